@@ -2,22 +2,45 @@ use chumsky::Parser;
 use std::error::Error;
 use std::fmt::Display;
 
+mod args;
+mod collation;
+mod diff;
+mod encoding;
 pub mod env;
 mod expr;
+mod handle;
 pub mod parsing;
+mod store;
+mod trace;
+#[cfg(feature = "watch")]
+mod watch;
 
 use crate::Env;
 use expr::{Expr, Type};
+use parsing::SourceMap;
+use std::rc::Rc;
 
+/// Evaluates a single expression, applying reader macros first.
 pub fn eval_expr(input: &str, env: &mut Env) -> Result<Expr, LispError> {
-    let ast = parsing::parse_expr().parse(input).unwrap();
+    let (input, source_map) = SourceMap::expand(input);
+    let source_map = Rc::new(source_map);
+    let ast = parsing::parse_expr(source_map.clone())
+        .parse(input)
+        .unwrap();
+    env.note_dbg_locations(source_map.take_dbg_forms());
 
     let ast = ast.expand_all(env)?;
     ast.eval(env)
 }
 
+/// Evaluates every expression in a script, applying reader macros first.
 pub fn eval_script(input: &str, env: &mut Env) -> Result<Expr, LispError> {
-    let ast = parsing::parse_script().parse(input).unwrap();
+    let (input, source_map) = SourceMap::expand(input);
+    let source_map = Rc::new(source_map);
+    let ast = parsing::parse_script(source_map.clone())
+        .parse(input)
+        .unwrap();
+    env.note_dbg_locations(source_map.take_dbg_forms());
     for expr in &ast[..ast.len() - 1] {
         expr.expand_all(env)?.eval(env)?;
     }
@@ -38,9 +61,23 @@ pub enum LispError {
 
     /// Wrong number of arguments
     Arity,
+
+    /// Failure talking to the outside world, e.g. the filesystem.
+    Io(std::io::Error),
+
+    /// Argument of the right type, but with a value the builtin can't use.
+    InvalidArgument(String),
+
+    /// `assert=` failed, holds the pretty printed diff.
+    AssertionFailed(String),
 }
 
 impl Error for LispError {}
+impl From<std::io::Error> for LispError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
 impl Display for LispError {
     fn fmt(&self, mut f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -59,6 +96,9 @@ impl Display for LispError {
                 write!(&mut f, "Wrong number of forms expected for lambda form")
                 // FIXME: I use this for stuff that isn't accurately explained by this error message.
             }
+            Self::Io(err) => write!(&mut f, "IO error: {}", err),
+            Self::InvalidArgument(reason) => write!(&mut f, "Invalid argument, {}", reason),
+            Self::AssertionFailed(diff) => write!(&mut f, "Assertion failed\n{}", diff),
         }
     }
 }
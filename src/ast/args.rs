@@ -0,0 +1,278 @@
+//! Declarative command line parsing for `parse-args`.
+//!
+//! A spec is a list of entries:
+//! - `(flag name "help")`, `--name` sets name to true.
+//! - `(option name default "help")`, `--name value` or `--name=value`,
+//!   read as a float, bool or string to match the default.
+//! - `(positional name "help")`, required, filled in order.
+//!
+//! The result is an association list of every name to its value,
+//! plus `help?` (whether `--help` was passed) and `usage`, the generated help text.
+use super::{
+    expr::{Expr, Type},
+    LispError,
+};
+
+enum Kind {
+    Flag,
+    Option(Expr),
+    Positional,
+}
+
+struct Entry {
+    name: String,
+    kind: Kind,
+    help: String,
+}
+
+fn invalid(reason: String) -> LispError {
+    LispError::InvalidArgument(reason)
+}
+
+fn parse_spec(spec: &Expr) -> Result<Vec<Entry>, LispError> {
+    let entries = match spec {
+        Expr::List(entries) => entries,
+        not_a_list => return Err(LispError::TypeMismatch(Type::List, not_a_list.clone())),
+    };
+    entries
+        .iter()
+        .map(|entry| {
+            let entry = match entry {
+                Expr::List(entry) => entry,
+                not_a_list => return Err(LispError::TypeMismatch(Type::List, not_a_list.clone())),
+            };
+            let (kind, name, rest) = match &entry[..] {
+                [Expr::Symbol(kind), Expr::Symbol(name), rest @ ..] => (kind, name, rest),
+                _ => {
+                    return Err(invalid(format!(
+                        "malformed spec entry {}",
+                        Expr::List(entry.clone())
+                    )))
+                }
+            };
+            let (kind, help) = match (kind.as_str(), rest) {
+                ("flag", help) => (Kind::Flag, help),
+                ("option", [default, help @ ..]) => (Kind::Option(default.clone()), help),
+                ("positional", help) => (Kind::Positional, help),
+                _ => {
+                    return Err(invalid(format!(
+                        "unknown spec entry kind {kind}, expected flag, option or positional"
+                    )))
+                }
+            };
+            let help = match help {
+                [] => String::new(),
+                [Expr::String(help)] => help.clone(),
+                _ => return Err(invalid(format!("malformed help for {name}"))),
+            };
+            Ok(Entry {
+                name: name.clone(),
+                kind,
+                help,
+            })
+        })
+        .collect()
+}
+
+/// Reads an option's value as the same type as its default.
+fn option_value(name: &str, default: &Expr, value: String) -> Result<Expr, LispError> {
+    let parsed = match default {
+        Expr::Float(_) => value.parse().map(Expr::Float).ok(),
+        Expr::Bool(_) => value.parse().map(Expr::Bool).ok(),
+        _ => return Ok(Expr::String(value)),
+    };
+    parsed.ok_or_else(|| {
+        invalid(format!(
+            "--{name} expects a value like {default}, got {value:?}"
+        ))
+    })
+}
+
+fn usage(entries: &[Entry]) -> String {
+    let positionals: Vec<&Entry> = entries
+        .iter()
+        .filter(|e| matches!(e.kind, Kind::Positional))
+        .collect();
+    let mut usage = String::from("usage: [options]");
+    for entry in &positionals {
+        usage.push_str(&format!(" <{}>", entry.name));
+    }
+
+    usage.push_str("\n\noptions:\n");
+    let mut lines = vec![];
+    for entry in entries {
+        match &entry.kind {
+            Kind::Flag => lines.push((format!("--{}", entry.name), entry.help.clone())),
+            Kind::Option(default) => lines.push((
+                format!("--{} <value>", entry.name),
+                format!("{} (default: {})", entry.help, default)
+                    .trim_start()
+                    .to_string(),
+            )),
+            Kind::Positional => (),
+        }
+    }
+    lines.push(("--help".to_string(), "Show this help".to_string()));
+    let width = entries
+        .iter()
+        .map(|e| e.name.len() + 10)
+        .chain(Some(8))
+        .max()
+        .unwrap_or(0);
+    for (left, right) in &lines {
+        usage.push_str(&format!("  {left:width$}  {right}\n"));
+    }
+
+    if !positionals.is_empty() {
+        usage.push_str("\narguments:\n");
+        for entry in &positionals {
+            usage.push_str(&format!("  {:width$}  {}\n", entry.name, entry.help));
+        }
+    }
+    usage
+}
+
+pub fn parse_args(spec: &Expr, args: &[String]) -> Result<Expr, LispError> {
+    let entries = parse_spec(spec)?;
+    let mut values: Vec<(String, Option<Expr>)> = entries
+        .iter()
+        .map(|entry| {
+            let value = match &entry.kind {
+                Kind::Flag => Some(Expr::Bool(false)),
+                Kind::Option(default) => Some(default.clone()),
+                Kind::Positional => None,
+            };
+            (entry.name.clone(), value)
+        })
+        .collect();
+    let mut positionals = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| matches!(e.kind, Kind::Positional))
+        .map(|(i, _)| i);
+    let mut help_requested = false;
+    let mut options_ended = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" if !options_ended => {
+                options_ended = true;
+                continue;
+            }
+            "--help" if !options_ended => {
+                help_requested = true;
+                continue;
+            }
+            _ => (),
+        }
+        let long = arg.strip_prefix("--").filter(|_| !options_ended);
+        let Some(long) = long else {
+            let i = positionals
+                .next()
+                .ok_or_else(|| invalid(format!("unexpected argument {arg:?}")))?;
+            values[i].1 = Some(Expr::String(arg.clone()));
+            continue;
+        };
+        let (name, inline_value) = match long.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (long, None),
+        };
+        let i = entries
+            .iter()
+            .position(|e| e.name == name && !matches!(e.kind, Kind::Positional))
+            .ok_or_else(|| invalid(format!("unknown option --{name}")))?;
+        values[i].1 = Some(match (&entries[i].kind, inline_value) {
+            (Kind::Flag, None) => Expr::Bool(true),
+            (Kind::Flag, Some(_)) => return Err(invalid(format!("--{name} doesn't take a value"))),
+            (Kind::Option(default), inline_value) => {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => args
+                        .next()
+                        .ok_or_else(|| invalid(format!("--{name} expects a value")))?
+                        .clone(),
+                };
+                option_value(name, default, value)?
+            }
+            (Kind::Positional, _) => unreachable!("positionals aren't looked up by name"),
+        });
+    }
+
+    let mut result = vec![];
+    for (name, value) in values {
+        let value = match value {
+            Some(value) => value,
+            None if help_requested => Expr::Bool(false),
+            None => return Err(invalid(format!("missing argument <{name}>"))),
+        };
+        result.push(Expr::List(vec![Expr::Symbol(name), value]));
+    }
+    result.push(Expr::List(vec![
+        Expr::Symbol("help?".to_string()),
+        Expr::Bool(help_requested),
+    ]));
+    result.push(Expr::List(vec![
+        Expr::Symbol("usage".to_string()),
+        Expr::String(usage(&entries)),
+    ]));
+    Ok(Expr::List(result))
+}
+
+#[test]
+fn parses_flags_options_and_positionals() {
+    let spec = super::eval_expr(
+        r#"(quote ((flag verbose "Print more")
+                   (option output "out.txt" "Where to write")
+                   (positional input "File to read")))"#,
+        &mut super::Env::default(),
+    )
+    .unwrap();
+    let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+    let parsed = parse_args(&spec, &args(&["--verbose", "in.txt", "--output=o.txt"])).unwrap();
+    let Expr::List(parsed) = parsed else {
+        panic!("expected a list")
+    };
+    let parsed: Vec<String> = parsed[..3].iter().map(ToString::to_string).collect();
+    assert_eq!(
+        parsed,
+        [
+            r#"(verbose true)"#,
+            r#"(output "o.txt")"#,
+            r#"(input "in.txt")"#
+        ]
+    );
+
+    assert!(parse_args(&spec, &args(&[])).is_err());
+    assert!(parse_args(&spec, &args(&["a", "b"])).is_err());
+    assert!(parse_args(&spec, &args(&["--help"])).is_ok());
+    assert!(parse_args(&spec, &args(&["--", "--verbose"])).is_ok());
+}
+
+#[test]
+fn option_values_match_their_default_type() {
+    let spec = super::eval_expr(
+        r#"(quote ((option jobs 4 "Workers") (option color true "Colorize")))"#,
+        &mut super::Env::default(),
+    )
+    .unwrap();
+    let parse = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+        parse_args(&spec, &args).map(|parsed| match parsed {
+            Expr::List(parsed) => parsed[..2]
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            _ => panic!("expected a list"),
+        })
+    };
+
+    assert_eq!(parse(&[]).unwrap(), ["(jobs 4)", "(color true)"]);
+    assert_eq!(
+        parse(&["--jobs", "8", "--color=false"]).unwrap(),
+        ["(jobs 8)", "(color false)"]
+    );
+    assert!(parse(&["--jobs", "many"]).is_err());
+    assert!(parse(&["--color=maybe"]).is_err());
+}
@@ -0,0 +1,117 @@
+//! String ordering options for `sort` and `compare`.
+use super::{
+    expr::{Expr, Type},
+    LispError,
+};
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Collation {
+    /// Compare letters by their lowercase form.
+    pub ignore_case: bool,
+    /// Compare runs of digits by their value, so "file2" sorts before "file10".
+    pub numeric: bool,
+}
+
+impl Collation {
+    /// Splits trailing `:option` symbols off the end of `args`.
+    pub fn from_args(args: &[Expr]) -> Result<(&[Expr], Collation), LispError> {
+        let mut collation = Collation::default();
+        let mut end = args.len();
+        for (i, arg) in args.iter().enumerate().rev() {
+            let s = match arg {
+                Expr::Symbol(s) if s.starts_with(':') => s,
+                _ => break,
+            };
+            match s.as_str() {
+                ":ignore-case" => collation.ignore_case = true,
+                ":numeric" => collation.numeric = true,
+                _ => {
+                    return Err(LispError::InvalidArgument(format!(
+                        "unknown collation option {s}, expected :ignore-case or :numeric"
+                    )))
+                }
+            }
+            end = i;
+        }
+        Ok((&args[..end], collation))
+    }
+
+    pub fn compare(&self, a: &Expr, b: &Expr) -> Result<Ordering, LispError> {
+        match (a, b) {
+            (Expr::Float(a), Expr::Float(b)) => Ok(a.total_cmp(b)),
+            (Expr::String(a), Expr::String(b)) => Ok(self.compare_str(a, b)),
+            // The left operand decides what the right one should have been.
+            (Expr::Float(_), not_a_float) => {
+                Err(LispError::TypeMismatch(Type::Float, not_a_float.clone()))
+            }
+            (Expr::String(_), not_a_string) | (not_a_string, _) => {
+                Err(LispError::TypeMismatch(Type::String, not_a_string.clone()))
+            }
+        }
+    }
+
+    pub fn compare_str(&self, a: &str, b: &str) -> Ordering {
+        let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+        // Leading zeros only matter if the strings are otherwise equal.
+        let mut zeros = Ordering::Equal;
+        loop {
+            let ordering = match (a.peek(), b.peek()) {
+                (None, None) => return zeros,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(x), Some(y)) if self.numeric && x.is_ascii_digit() && y.is_ascii_digit() => {
+                    let (x, y) = (take_digits(&mut a), take_digits(&mut b));
+                    let (x_value, y_value) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                    zeros = zeros.then(x.len().cmp(&y.len()));
+                    x_value
+                        .len()
+                        .cmp(&y_value.len())
+                        .then_with(|| x_value.cmp(y_value))
+                }
+                (Some(&x), Some(&y)) => {
+                    a.next();
+                    b.next();
+                    if self.ignore_case {
+                        x.to_lowercase().cmp(y.to_lowercase())
+                    } else {
+                        x.cmp(&y)
+                    }
+                }
+            };
+            if ordering.is_ne() {
+                return ordering;
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+#[test]
+fn numeric_and_case_insensitive_ordering() {
+    let natural = Collation {
+        ignore_case: true,
+        numeric: true,
+    };
+    let mut files = vec!["file10", "File2", "file1", "file02", "FILE2b"];
+    files.sort_by(|a, b| natural.compare_str(a, b));
+    assert_eq!(files, ["file1", "File2", "file02", "FILE2b", "file10"]);
+    assert_eq!(natural.compare_str("file2", "file02"), Ordering::Less);
+
+    let plain = Collation::default();
+    assert_eq!(plain.compare_str("file10", "file2"), Ordering::Less);
+    assert_eq!(plain.compare_str("B", "a"), Ordering::Less);
+
+    let mismatch = plain.compare(&Expr::Float(1.0), &Expr::String("a".to_string()));
+    assert!(matches!(
+        mismatch,
+        Err(LispError::TypeMismatch(Type::Float, Expr::String(_)))
+    ));
+}
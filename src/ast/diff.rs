@@ -0,0 +1,98 @@
+//! Structural diffing of values, used by `diff` and `assert=`.
+use super::expr::Expr;
+use std::{fmt, ptr, rc::Rc};
+
+/// A single place where two values disagree.
+/// `left`/`right` are `None` when one list is shorter than the other.
+pub struct Difference {
+    pub path: Vec<usize>,
+    pub left: Option<Expr>,
+    pub right: Option<Expr>,
+}
+
+pub fn diff(left: &Expr, right: &Expr) -> Vec<Difference> {
+    let mut differences = vec![];
+    diff_at(&mut vec![], left, right, &mut differences);
+    differences
+}
+
+fn diff_at(path: &mut Vec<usize>, left: &Expr, right: &Expr, out: &mut Vec<Difference>) {
+    match (left, right) {
+        (Expr::List(l), Expr::List(r)) => {
+            for i in 0..l.len().max(r.len()) {
+                path.push(i);
+                match (l.get(i), r.get(i)) {
+                    (Some(l), Some(r)) => diff_at(path, l, r, out),
+                    (l, r) => out.push(Difference {
+                        path: path.clone(),
+                        left: l.cloned(),
+                        right: r.cloned(),
+                    }),
+                }
+                path.pop();
+            }
+        }
+        (l, r) if !equal(l, r) => out.push(Difference {
+            path: path.clone(),
+            left: Some(l.clone()),
+            right: Some(r.clone()),
+        }),
+        _ => (),
+    }
+}
+
+pub fn equal(left: &Expr, right: &Expr) -> bool {
+    use Expr::*;
+
+    match (left, right) {
+        (Symbol(l), Symbol(r)) | (String(l), String(r)) => l == r,
+        (Bytes(l), Bytes(r)) => l == r,
+        (Float(l), Float(r)) => l == r,
+        (Bool(l), Bool(r)) => l == r,
+        (Handle(l), Handle(r)) => l.ptr_eq(r),
+        (List(l), List(r)) => l.len() == r.len() && l.iter().zip(r).all(|(l, r)| equal(l, r)),
+        (Fn(l), Fn(r)) => ptr::fn_addr_eq(*l, *r),
+        (Lambda(l), Lambda(r)) => {
+            Rc::ptr_eq(&l.body, &r.body) && Rc::ptr_eq(&l.bindings, &r.bindings)
+        }
+        (Macro(l), Macro(r)) => {
+            Rc::ptr_eq(&l.body, &r.body) && Rc::ptr_eq(&l.bindings, &r.bindings)
+        }
+        _ => false,
+    }
+}
+
+impl Difference {
+    /// Converts into `(path left right)`, with `missing` standing in for absent elements.
+    pub fn to_expr(&self) -> Expr {
+        let side =
+            |side: &Option<Expr>| side.clone().unwrap_or(Expr::Symbol("missing".to_string()));
+        let path = self.path.iter().map(|&i| Expr::Float(i as f64)).collect();
+        Expr::List(vec![Expr::List(path), side(&self.left), side(&self.right)])
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<std::string::String> = self.path.iter().map(ToString::to_string).collect();
+        writeln!(f, "at ({}):", path.join(" "))?;
+        if let Some(left) = &self.left {
+            writeln!(f, "- {}", left)?;
+        }
+        if let Some(right) = &self.right {
+            writeln!(f, "+ {}", right)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn diff_reports_nested_and_missing_elements() {
+    let left = super::eval_expr("(quote (1 (2 3) 4))", &mut super::Env::default()).unwrap();
+    let right = super::eval_expr("(quote (1 (2 5)))", &mut super::Env::default()).unwrap();
+    let differences: Vec<_> = diff(&left, &right)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(differences, ["at (1 1):\n- 3\n+ 5\n", "at (2):\n- 4\n"]);
+}
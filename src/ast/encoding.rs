@@ -0,0 +1,91 @@
+//! Text encodings for strings and bytes, used by the encoding builtins.
+use super::LispError;
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Decodes two hex digits, unlike `u8::from_str_radix` which also accepts a leading `+`.
+fn hex_byte(pair: &[u8]) -> Option<u8> {
+    match pair {
+        [hi, lo] if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+            let digits = std::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(digits, 16).ok()
+        }
+        _ => None,
+    }
+}
+
+pub fn hex_decode(input: &str) -> Result<Vec<u8>, LispError> {
+    input
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            hex_byte(pair)
+                .ok_or_else(|| LispError::InvalidArgument(format!("{input:?} is not valid hex")))
+        })
+        .collect()
+}
+
+pub fn base64_encode(bytes: &[u8]) -> String {
+    STANDARD.encode(bytes)
+}
+
+pub fn base64_decode(input: &str) -> Result<Vec<u8>, LispError> {
+    STANDARD
+        .decode(input)
+        .map_err(|err| LispError::InvalidArgument(format!("{input:?} is not valid base64: {err}")))
+}
+
+/// Percent-encodes everything except the unreserved characters of RFC 3986.
+pub fn url_encode(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len());
+    for &b in bytes {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(b as char)
+            }
+            _ => result.push_str(&format!("%{b:02X}")),
+        }
+    }
+    result
+}
+
+pub fn url_decode(input: &str) -> Result<String, LispError> {
+    let invalid = || LispError::InvalidArgument(format!("{input:?} is not valid url encoding"));
+    let bytes = input.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3).ok_or_else(invalid)?;
+            result.push(hex_byte(hex).ok_or_else(invalid)?);
+            i += 3;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(result).map_err(|_| invalid())
+}
+
+#[test]
+fn encodings_round_trip() {
+    let input = "wilf & friends/?";
+    assert_eq!(
+        hex_decode(&hex_encode(input.as_bytes())).unwrap(),
+        input.as_bytes()
+    );
+    assert_eq!(base64_encode(input.as_bytes()), "d2lsZiAmIGZyaWVuZHMvPw==");
+    assert_eq!(
+        base64_decode("d2lsZiAmIGZyaWVuZHMvPw==").unwrap(),
+        input.as_bytes()
+    );
+    assert_eq!(url_encode(input.as_bytes()), "wilf%20%26%20friends%2F%3F");
+    assert_eq!(url_decode("wilf%20%26%20friends%2F%3F").unwrap(), input);
+    assert!(hex_decode("abc").is_err());
+    assert!(url_decode("%zz").is_err());
+    assert!(hex_decode("+f+f").is_err());
+    assert!(url_decode("%+f").is_err());
+}
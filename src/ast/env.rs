@@ -1,9 +1,22 @@
 use super::{
-    expr::{eval_forms, Expr, Lambda, Macro, Type},
+    args,
+    collation::Collation,
+    diff, encoding,
+    expr::{eval_forms, Expr, Lambda, Location, Macro, Type},
+    handle::{FileResource, Handle},
+    store::Store,
+    trace::Trace,
     LispError,
 };
 use rustc_hash::FxHashMap as HashMap;
-use std::{io::Write, rc::Rc, time::Instant};
+use std::{
+    cell::{Cell, RefCell},
+    env as std_env, fmt,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
 macro_rules! tonicity {
     ($op:tt) => {{
@@ -36,6 +49,29 @@ fn parse_bools(list: &[Expr], env: &mut Env) -> Result<Vec<bool>, LispError> {
         .collect()
 }
 
+fn parse_string(expr: &Expr, env: &mut Env) -> Result<String, LispError> {
+    match expr.eval(env)? {
+        Expr::String(s) => Ok(s),
+        not_a_string => Err(LispError::TypeMismatch(Type::String, not_a_string)),
+    }
+}
+
+/// Evaluates to raw bytes, accepting strings as their UTF-8 encoding.
+fn parse_bytes(expr: &Expr, env: &mut Env) -> Result<Vec<u8>, LispError> {
+    match expr.eval(env)? {
+        Expr::Bytes(b) => Ok(b),
+        Expr::String(s) => Ok(s.into_bytes()),
+        not_bytes => Err(LispError::TypeMismatch(Type::Bytes, not_bytes)),
+    }
+}
+
+fn parse_handle(expr: &Expr, env: &mut Env) -> Result<Handle, LispError> {
+    match expr.eval(env)? {
+        Expr::Handle(h) => Ok(h),
+        not_a_handle => Err(LispError::TypeMismatch(Type::Handle, not_a_handle)),
+    }
+}
+
 macro_rules! env {
     ($($k:expr => $v:expr),+ $(,)? ) => {{
         let mut map: ::rustc_hash::FxHashMap<String, Expr>  = ::rustc_hash::FxHashMap::default();
@@ -46,7 +82,8 @@ macro_rules! env {
 
 impl<'a> Default for Env<'a> {
     fn default() -> Env<'a> {
-        let data = env!(
+        #[allow(unused_mut)]
+        let mut data = env!(
         "=" => tonicity!(==),
         "<" => tonicity!(<),
         ">" => tonicity!(>),
@@ -187,43 +224,288 @@ impl<'a> Default for Env<'a> {
                 }?;
                 let evaluated = value.eval(&mut env)?;
                 env.data.insert(symbol, evaluated);
-                Ok(())
-            }).try_collect()?;
+                Ok::<_, LispError>(())
+            }).try_collect::<()>()?;
 
             body.eval(&mut env)
         },
         "dbg" =>
         |args, env| {
             if args.len() != 1 { return Err(LispError::Arity) };
-            let result = args[0].eval(env);
-            dbg!(&result);
-            result
+            let result = args[0].eval(env)?;
+            env.print_dbg(&args[0], &result);
+            Ok(result)
         },
         "print" =>
         |args, env| {
             if args.len() != 1 { return Err(LispError::Arity) };
             let result = args[0].eval(env)?;
-            print!("{}", result);
+            let _ = write!(env.io.stdout.borrow_mut(), "{}", result);
             Ok(result)
         },
         "println" =>
         |args, env| {
             if args.len() != 1 { return Err(LispError::Arity) };
             let result = args[0].eval(env)?;
-            println!("{}", result);
+            let _ = writeln!(env.io.stdout.borrow_mut(), "{}", result);
             Ok(result)
         },
         "readline" =>
-        |args, _env| {
+        |args, env| {
             if args.len() > 1 { return Err(LispError::Arity) };
             if let Some(Expr::String(s)) = args.get(0) {
-                print!("{s}");
-                let _ = std::io::stdout().flush();
+                let mut stdout = env.io.stdout.borrow_mut();
+                let _ = write!(stdout, "{s}");
+                let _ = stdout.flush();
+            }
+            env.trace.borrow_mut().nondeterministic("readline", |_| {
+                let mut buf = String::with_capacity(256);
+                let _ = std::io::stdin().read_line(&mut buf);
+                buf = String::from(buf.trim_end());
+                Ok(Expr::String(buf))
+            })
+        },
+        "rand" =>
+        |args, env| {
+            if !args.is_empty() { return Err(LispError::Arity) };
+            env.trace.borrow_mut().nondeterministic("rand", |trace| Ok(Expr::Float(trace.random())))
+        },
+        "now-millis" =>
+        |args, env| {
+            if !args.is_empty() { return Err(LispError::Arity) };
+            env.trace.borrow_mut().nondeterministic("now-millis", |_| {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                Ok(Expr::Float(now.as_millis() as f64))
+            })
+        },
+        "getenv" =>
+        |args, env| {
+            let (name, default) = match args {
+                [name] => (parse_string(name, env)?, String::new()),
+                [name, default] => (parse_string(name, env)?, parse_string(default, env)?),
+                _ => return Err(LispError::Arity),
+            };
+            env.trace.borrow_mut().nondeterministic("getenv", |_| {
+                Ok(Expr::String(std_env::var(&name).unwrap_or(default)))
+            })
+        },
+        "expand-path" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            let path = parse_string(&args[0], env)?;
+            env.trace.borrow_mut().nondeterministic("expand-path", |_| {
+                Ok(Expr::String(expand_path(&path)?))
+            })
+        },
+        "home-dir" =>
+        |args, env| {
+            if !args.is_empty() { return Err(LispError::Arity) };
+            env.trace.borrow_mut().nondeterministic("home-dir", |_| Ok(path_to_expr(&home_dir()?)))
+        },
+        "config-dir" =>
+        |args, env| {
+            if !args.is_empty() { return Err(LispError::Arity) };
+            env.trace.borrow_mut().nondeterministic("config-dir", |_| {
+                let dir = dirs_next::config_dir().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "could not determine config directory")
+                })?;
+                Ok(path_to_expr(&dir))
+            })
+        },
+        "string->bytes" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::Bytes(parse_string(&args[0], env)?.into_bytes()))
+        },
+        "bytes->string" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            let bytes = parse_bytes(&args[0], env)?;
+            let string = String::from_utf8(bytes)
+                .map_err(|_| LispError::InvalidArgument("bytes are not valid UTF-8".to_string()))?;
+            Ok(Expr::String(string))
+        },
+        "base64-encode" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::String(encoding::base64_encode(&parse_bytes(&args[0], env)?)))
+        },
+        "base64-decode" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::Bytes(encoding::base64_decode(&parse_string(&args[0], env)?)?))
+        },
+        "hex-encode" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::String(encoding::hex_encode(&parse_bytes(&args[0], env)?)))
+        },
+        "hex-decode" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::Bytes(encoding::hex_decode(&parse_string(&args[0], env)?)?))
+        },
+        "url-encode" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::String(encoding::url_encode(&parse_bytes(&args[0], env)?)))
+        },
+        "url-decode" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::String(encoding::url_decode(&parse_string(&args[0], env)?)?))
+        },
+        "get" =>
+        |args, env| {
+            if args.len() != 2 { return Err(LispError::Arity) };
+            let list = match args[0].eval(env)? {
+                Expr::List(list) => list,
+                not_a_list => return Err(LispError::TypeMismatch(Type::List, not_a_list)),
+            };
+            let key = args[1].eval(env)?;
+            list.into_iter()
+                .find_map(|entry| match entry {
+                    Expr::List(mut pair) if pair.len() == 2 && diff::equal(&pair[0], &key) => pair.pop(),
+                    _ => None,
+                })
+                .ok_or_else(|| LispError::InvalidArgument(format!("no entry for {key}")))
+        },
+        "parse-args" =>
+        |args, env| {
+            if args.len() != 2 { return Err(LispError::Arity) };
+            let spec = args[0].eval(env)?;
+            let cli_args = match args[1].eval(env)? {
+                Expr::List(list) => list
+                    .into_iter()
+                    .map(|arg| match arg {
+                        Expr::String(s) => Ok(s),
+                        not_a_string => Err(LispError::TypeMismatch(Type::String, not_a_string)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                not_a_list => return Err(LispError::TypeMismatch(Type::List, not_a_list)),
+            };
+            args::parse_args(&spec, &cli_args)
+        },
+        "compare" =>
+        |args, env| {
+            let (args, collation) = Collation::from_args(args)?;
+            if args.len() != 2 { return Err(LispError::Arity) };
+            let ordering = collation.compare(&args[0].eval(env)?, &args[1].eval(env)?)?;
+            Ok(Expr::Float(ordering as i8 as f64))
+        },
+        "sort" =>
+        |args, env| {
+            let (args, collation) = Collation::from_args(args)?;
+            if args.len() != 1 { return Err(LispError::Arity) };
+            let mut list = match args[0].eval(env)? {
+                Expr::List(list) => list,
+                not_a_list => return Err(LispError::TypeMismatch(Type::List, not_a_list)),
+            };
+            // Check everything is comparable up front, so the sort itself can't fail.
+            for pair in list.windows(2) {
+                collation.compare(&pair[0], &pair[1])?;
+            }
+            list.sort_by(|a, b| collation.compare(a, b).unwrap_or(std::cmp::Ordering::Equal));
+            Ok(Expr::List(list))
+        },
+        "diff" =>
+        |args, env| {
+            if args.len() != 2 { return Err(LispError::Arity) };
+            let differences = diff::diff(&args[0].eval(env)?, &args[1].eval(env)?);
+            Ok(Expr::List(differences.iter().map(diff::Difference::to_expr).collect()))
+        },
+        "print-diff" =>
+        |args, env| {
+            if args.len() != 2 { return Err(LispError::Arity) };
+            let differences = diff::diff(&args[0].eval(env)?, &args[1].eval(env)?);
+            let mut stdout = env.io.stdout.borrow_mut();
+            for difference in &differences {
+                let _ = write!(stdout, "{}", difference);
+            }
+            Ok(Expr::List(differences.iter().map(diff::Difference::to_expr).collect()))
+        },
+        "assert=" =>
+        |args, env| {
+            if args.len() != 2 { return Err(LispError::Arity) };
+            let differences = diff::diff(&args[0].eval(env)?, &args[1].eval(env)?);
+            if differences.is_empty() {
+                return Ok(Expr::Bool(true));
+            }
+            let pretty: Vec<String> = differences.iter().map(ToString::to_string).collect();
+            Err(LispError::AssertionFailed(pretty.concat()))
+        },
+        "file-open" =>
+        |args, env| {
+            let (path, mode) = match args {
+                [path] => (parse_string(path, env)?, "r".to_string()),
+                [path, mode] => (parse_string(path, env)?, parse_string(mode, env)?),
+                _ => return Err(LispError::Arity),
+            };
+            Ok(Expr::Handle(Handle::new(FileResource::open(&path, &mode)?)))
+        },
+        "file-read" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            let handle = parse_handle(&args[0], env)?;
+            Ok(Expr::String(handle.with(FileResource::read_to_string)?))
+        },
+        "file-write!" =>
+        |args, env| {
+            if args.len() != 2 { return Err(LispError::Arity) };
+            let handle = parse_handle(&args[0], env)?;
+            let s = parse_string(&args[1], env)?;
+            handle.with(|file: &mut FileResource| file.write(&s))?;
+            Ok(Expr::Handle(handle))
+        },
+        "close!" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::Bool(parse_handle(&args[0], env)?.close()?))
+        },
+        "closed?" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::Bool(parse_handle(&args[0], env)?.is_closed()))
+        },
+        "store-open" =>
+        |args, env| {
+            if args.len() != 1 { return Err(LispError::Arity) };
+            let path = parse_string(&args[0], env)?;
+            Ok(Expr::Handle(Handle::new(Store::open(&path)?)))
+        },
+        "store-get" =>
+        |args, env| {
+            if !(2..=3).contains(&args.len()) { return Err(LispError::Arity) };
+            let store = parse_handle(&args[0], env)?;
+            let key = args[1].eval(env)?;
+            match (store.with(|store: &mut Store| Ok(store.get(&key)))?, args.get(2)) {
+                (Some(value), _) => Ok(value),
+                (None, Some(default)) => default.eval(env),
+                (None, None) => Err(LispError::InvalidArgument(format!("no entry for {key}"))),
             }
-            let mut buf = String::with_capacity(256);
-            let _ = std::io::stdin().read_line(&mut buf);
-            buf = String::from(buf.trim_end());
-            Ok(Expr::String(buf))
+        },
+        "store-put!" =>
+        |args, env| {
+            if args.len() != 3 { return Err(LispError::Arity) };
+            let store = parse_handle(&args[0], env)?;
+            let key = args[1].eval(env)?;
+            let value = args[2].eval(env)?;
+            store.with(|store: &mut Store| store.put(key, value.clone()))?;
+            Ok(value)
+        },
+        "runtime-stats" =>
+        |args, env| {
+            if !args.is_empty() { return Err(LispError::Arity) };
+            let stats = env.stats();
+            let entry = |name: &str, n: u64| {
+                Expr::List(vec![Expr::Symbol(name.to_string()), Expr::Float(n as f64)])
+            };
+            Ok(Expr::List(vec![
+                entry("forms-evaluated", stats.forms_evaluated),
+                entry("lists-allocated", stats.lists_allocated),
+                entry("max-env-depth", stats.max_env_depth),
+                entry("macro-expansions", stats.macro_expansions),
+            ]))
         },
         "time" =>
         |args, env| {
@@ -232,12 +514,71 @@ impl<'a> Default for Env<'a> {
             let result = args[0].eval(env)?;
             let end = Instant::now();
             let difference = end - start;
-            println!("Eval time for expr: {} = {:?}", args[0], difference);
+            let _ = writeln!(env.io.stdout.borrow_mut(), "Eval time for expr: {} = {:?}", args[0], difference);
             Ok(result)
         },
         );
 
-        Env { data, outer: None }
+        #[cfg(feature = "hashing")]
+        data.extend(env!(
+        "md5" =>
+        |args, env| {
+            use md5::{Digest, Md5};
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::String(encoding::hex_encode(&Md5::digest(parse_bytes(&args[0], env)?))))
+        },
+        "sha1" =>
+        |args, env| {
+            use sha1::{Digest, Sha1};
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::String(encoding::hex_encode(&Sha1::digest(parse_bytes(&args[0], env)?))))
+        },
+        "sha256" =>
+        |args, env| {
+            use sha2::{Digest, Sha256};
+            if args.len() != 1 { return Err(LispError::Arity) };
+            Ok(Expr::String(encoding::hex_encode(&Sha256::digest(parse_bytes(&args[0], env)?))))
+        },
+        ));
+
+        #[cfg(feature = "watch")]
+        data.extend(env!(
+        "watch-path" =>
+        |args, env| {
+            use super::watch::Watcher;
+            let (path, handler) = match args {
+                [path] => (parse_string(path, env)?, None),
+                [path, handler] => (parse_string(path, env)?, Some(handler.eval(env)?)),
+                _ => return Err(LispError::Arity),
+            };
+            let watcher = Handle::new(Watcher::new(&path)?);
+            let Some(handler) = handler else { return Ok(Expr::Handle(watcher)) };
+            // Keep delivering events until the handler returns false.
+            loop {
+                let event = watcher.with(Watcher::next)?;
+                if let Expr::Bool(false) = handler.apply(vec![event], env)? {
+                    watcher.close()?;
+                    return Ok(Expr::Bool(true));
+                }
+            }
+        },
+        "watch-next" =>
+        |args, env| {
+            use super::watch::Watcher;
+            if args.len() != 1 { return Err(LispError::Arity) };
+            parse_handle(&args[0], env)?.with(Watcher::next)
+        },
+        ));
+
+        Env {
+            data,
+            outer: None,
+            io: Io::default(),
+            stats: Rc::default(),
+            trace: Rc::default(),
+            dbg_locations: Rc::default(),
+            depth: 0,
+        }
     }
 }
 
@@ -245,16 +586,148 @@ impl<'a> Default for Env<'a> {
 pub struct Env<'a> {
     pub(super) data: HashMap<String, Expr>,
     pub(super) outer: Option<&'a Env<'a>>,
+    pub(super) io: Io,
+    /// Shared with every inner scope, so the root sees counts for the whole run.
+    pub(super) stats: Rc<Cell<Stats>>,
+    /// Where nondeterministic builtins record to or replay from.
+    pub(super) trace: Rc<RefCell<Trace>>,
+    /// Where each `(dbg form)` read so far came from, keyed by the printed form.
+    pub(super) dbg_locations: Rc<RefCell<HashMap<String, Vec<Location>>>>,
+    /// How many scopes deep this environment is, the root being 0.
+    pub(super) depth: u64,
+}
+
+/// Counters for how much work the interpreter has done.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Stats {
+    pub forms_evaluated: u64,
+    /// Lists produced by evaluating a form.
+    pub lists_allocated: u64,
+    pub max_env_depth: u64,
+    pub macro_expansions: u64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "forms evaluated: {}", self.forms_evaluated)?;
+        writeln!(f, "lists allocated: {}", self.lists_allocated)?;
+        writeln!(f, "max env depth: {}", self.max_env_depth)?;
+        write!(f, "macro expansions: {}", self.macro_expansions)
+    }
+}
+
+/// Output streams shared between an environment and all of its inner scopes.
+#[derive(Clone)]
+pub struct Io {
+    pub stdout: Rc<RefCell<dyn Write>>,
+    pub stderr: Rc<RefCell<dyn Write>>,
+}
+
+impl Default for Io {
+    fn default() -> Self {
+        Io {
+            stdout: Rc::new(RefCell::new(io::stdout())),
+            stderr: Rc::new(RefCell::new(io::stderr())),
+        }
+    }
+}
+
+impl fmt::Debug for Io {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Io").finish_non_exhaustive()
+    }
 }
 
 impl Env<'_> {
     fn with_outer<'a>(env: &'a Env<'_>) -> Env<'a> {
+        env.record_depth(env.depth + 1);
         Env {
             outer: Some(env),
             data: HashMap::default(),
+            io: env.io.clone(),
+            stats: env.stats.clone(),
+            trace: env.trace.clone(),
+            dbg_locations: env.dbg_locations.clone(),
+            depth: env.depth + 1,
         }
     }
 
+    /// Binds the command line arguments passed to a script as `*args*`.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        let args = args.into_iter().map(Expr::String).collect();
+        self.data.insert("*args*".to_string(), Expr::List(args));
+    }
+
+    /// Records the results of nondeterministic builtins to `path`.
+    pub fn record_trace(&mut self, path: &Path) -> Result<(), LispError> {
+        *self.trace.borrow_mut() = Trace::record(path)?;
+        Ok(())
+    }
+
+    /// Replays the results of nondeterministic builtins from a trace made by [`Env::record_trace`].
+    pub fn replay_trace(&mut self, path: &Path) -> Result<(), LispError> {
+        *self.trace.borrow_mut() = Trace::replay(path)?;
+        Ok(())
+    }
+
+    /// Fails if a replay didn't use every recorded result.
+    pub fn finish_trace(&self) -> Result<(), LispError> {
+        self.trace.borrow().finish()
+    }
+
+    /// Remembers where `(dbg form)`s were read, as returned by [`SourceMap::take_dbg_forms`].
+    ///
+    /// [`SourceMap::take_dbg_forms`]: super::parsing::SourceMap::take_dbg_forms
+    pub(super) fn note_dbg_locations(&self, forms: Vec<(String, Location)>) {
+        let mut locations = self.dbg_locations.borrow_mut();
+        for (form, at) in forms {
+            locations.entry(form).or_default().push(at);
+        }
+    }
+
+    /// Lets a dbg form whose macros were expanded be found by where it was read.
+    pub(super) fn alias_dbg_location(&self, read: &Expr, expanded: &Expr) {
+        let mut locations = self.dbg_locations.borrow_mut();
+        let (read, expanded) = (read.to_string(), expanded.to_string());
+        if read == expanded || locations.contains_key(&expanded) {
+            return;
+        }
+        if let Some(at) = locations.get(&read).cloned() {
+            locations.insert(expanded, at);
+        }
+    }
+
+    /// Writes `dbg` output to stderr, prefixed by where the form is in the source, if known.
+    /// A form written the same way in several places lists all of them.
+    pub(super) fn print_dbg(&self, form: &Expr, value: &Expr) {
+        let locations = self.dbg_locations.borrow();
+        let at: Vec<String> = locations
+            .get(&form.to_string())
+            .into_iter()
+            .flatten()
+            .map(ToString::to_string)
+            .collect();
+        let mut stderr = self.io.stderr.borrow_mut();
+        let _ = match &at[..] {
+            [] => writeln!(stderr, "{form} = {value}"),
+            at => writeln!(stderr, "[{}] {form} = {value}", at.join(", ")),
+        };
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats.get()
+    }
+
+    pub(super) fn record(&self, f: impl FnOnce(&mut Stats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    pub(super) fn record_depth(&self, depth: u64) {
+        self.record(|stats| stats.max_env_depth = stats.max_env_depth.max(depth));
+    }
+
     pub fn get(&self, k: &str) -> Option<Expr> {
         match self.data.get(k) {
             Some(exp) => Some(exp.clone()),
@@ -266,6 +739,57 @@ impl Env<'_> {
     }
 }
 
+fn home_dir() -> Result<PathBuf, LispError> {
+    dirs_next::home_dir().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "could not determine home directory",
+        )
+        .into()
+    })
+}
+
+fn path_to_expr(path: &Path) -> Expr {
+    Expr::String(path.to_string_lossy().into_owned())
+}
+
+/// Expands a leading `~` to the home directory and any `$VAR` or `${VAR}`
+/// to the value of that environment variable, or nothing if it's unset.
+fn expand_path(path: &str) -> Result<String, LispError> {
+    let mut result = String::with_capacity(path.len());
+    let rest = if path == "~" || path.starts_with("~/") {
+        result.push_str(&home_dir()?.to_string_lossy());
+        &path[1..]
+    } else {
+        path
+    };
+
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        let name: String = if chars.peek() == Some(&'{') {
+            chars.next();
+            chars.by_ref().take_while(|&c| c != '}').collect()
+        } else {
+            let mut name = String::new();
+            while let Some(c) = chars.next_if(|&c| c.is_alphanumeric() || c == '_') {
+                name.push(c);
+            }
+            name
+        };
+        if name.is_empty() {
+            result.push('$');
+        } else {
+            result.push_str(&std_env::var(&name).unwrap_or_default());
+        }
+    }
+
+    Ok(result)
+}
+
 fn quasiquote(args: &[Expr], env: &mut Env) -> Result<Expr, LispError> {
     use Expr::*;
 
@@ -303,3 +827,82 @@ fn quasiquote(args: &[Expr], env: &mut Env) -> Result<Expr, LispError> {
     }
     Ok(List(results.into_iter().rev().collect()))
 }
+
+#[test]
+fn dbg_writes_location_form_and_value_to_stderr() {
+    let stderr = Rc::new(RefCell::new(Vec::<u8>::new()));
+    let mut env = Env::default();
+    env.io.stderr = stderr.clone();
+
+    let script = "(def x '(a))\n(+ 1 (dbg (* 2 3)))\n(dbg '(dbg 1))";
+    let result = super::eval_script(script, &mut env).unwrap();
+    assert_eq!(result.to_string(), "(dbg 1)");
+    assert!(matches!(result, Expr::List(_)));
+    let output = String::from_utf8(stderr.borrow().clone()).unwrap();
+    assert_eq!(
+        output,
+        "[2:6] (* 2 3) = 6\n[3:1] (quote (dbg 1)) = (dbg 1)\n"
+    );
+
+    let script = "(dbg 7)\n(dbg 7)";
+    super::eval_script(script, &mut env).unwrap();
+    let output = String::from_utf8(stderr.borrow().clone()).unwrap();
+    assert!(output.ends_with("[1:1, 2:1] 7 = 7\n[1:1, 2:1] 7 = 7\n"));
+}
+
+#[test]
+fn dbg_lists_are_only_special_when_called() {
+    let eval = |script| {
+        let mut env = Env::default();
+        env.io.stderr = Rc::new(RefCell::new(Vec::<u8>::new()));
+        super::eval_script(script, &mut env).unwrap().to_string()
+    };
+
+    assert_eq!(eval("(let (dbg 3) dbg)"), "3");
+    assert_eq!(eval("(def f (fn (dbg x) (+ dbg x)))\n(f 1 2)"), "3");
+    assert_eq!(eval("(def dbg (fn (x) (* x 10)))\n(dbg 2)"), "20");
+    assert_eq!(
+        eval("(def x 5)\n(quasiquote (a (dbg (unquote x)) b))"),
+        "((a (dbg 5) b))"
+    );
+}
+
+#[test]
+fn expand_path_expands_tilde_and_vars() {
+    std_env::set_var("WILF_TEST_DIR", "dotfiles");
+    let home = home_dir().unwrap();
+    let home = home.to_string_lossy();
+
+    assert_eq!(expand_path("~").unwrap(), home);
+    assert_eq!(
+        expand_path("~/$WILF_TEST_DIR/${WILF_TEST_DIR}.d").unwrap(),
+        format!("{home}/dotfiles/dotfiles.d")
+    );
+    assert_eq!(expand_path("/etc/~/$").unwrap(), "/etc/~/$");
+}
+
+#[cfg(feature = "hashing")]
+#[test]
+fn hashes_strings_and_bytes() {
+    let mut env = Env::default();
+    let md5 = super::eval_expr(r#"(md5 "abc")"#, &mut env).unwrap();
+    assert_eq!(md5.to_string(), r#""900150983cd24fb0d6963f7d28e17f72""#);
+    let sha256 = super::eval_expr(r#"(sha256 (hex-decode "616263"))"#, &mut env).unwrap();
+    assert_eq!(
+        sha256.to_string(),
+        r#""ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad""#
+    );
+}
+
+#[test]
+fn stats_count_forms_depth_and_expansions() {
+    let mut env = Env::default();
+    let twice = "(def twice (macro (x) (quasiquote (do (unquote x) (unquote x)))))";
+    super::eval_expr(twice, &mut env).unwrap();
+    super::eval_expr("(let (f (fn (n) (* n 2))) (twice (f 1)))", &mut env).unwrap();
+
+    let stats = env.stats();
+    assert_eq!(stats.macro_expansions, 1);
+    assert_eq!(stats.max_env_depth, 3);
+    assert!(stats.forms_evaluated > 10);
+}
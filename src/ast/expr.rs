@@ -1,4 +1,4 @@
-use crate::ast::{Env, LispError};
+use crate::ast::{handle::Handle, Env, LispError};
 use rustc_hash::FxHashMap as HashMap;
 use std::{fmt, rc::Rc, string::ToString};
 
@@ -6,15 +6,20 @@ use std::{fmt, rc::Rc, string::ToString};
 pub enum Type {
     Fn,
     Symbol,
+    String,
+    Bytes,
     Float,
     List,
     Bool,
+    Handle,
 }
 
 #[derive(Clone)]
 pub enum Expr {
     Symbol(String),
     String(String),
+    Bytes(Vec<u8>),
+    Handle(Handle),
 
     Float(f64),
     Bool(bool),
@@ -26,6 +31,13 @@ pub enum Expr {
     Macro(Macro),
 }
 
+/// Line and column in the original source, both starting at 1.
+#[derive(Debug, Clone, Copy)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
 #[derive(Clone, Debug)]
 pub struct Lambda {
     /// Bindings in this context are the forms required by the lambda,
@@ -51,6 +63,7 @@ impl Expr {
             List(list) => match &list[..] {
                 [sym @ Symbol(_), args @ ..] => match sym.eval(env) {
                     Ok(Macro(m)) => {
+                        env.record(|stats| stats.macro_expansions += 1);
                         let mut new_env = create_scope(&m.bindings, args, env)?;
                         m.body.eval(&mut new_env)
                     }
@@ -67,11 +80,17 @@ impl Expr {
         use Expr::*;
 
         let result = match self {
-            List(list) => {
-                let mut list = list.clone();
+            List(read) => {
+                let mut list = read.clone();
                 for expr in list[1..].iter_mut() {
                     *expr = expr.expand_all(env)?
                 }
+                // Macros in a dbg form change how it prints, so keep it findable by its new text.
+                if let ([Symbol(s), form], [_, expanded]) = (&read[..], &list[..]) {
+                    if s == "dbg" {
+                        env.alias_dbg_location(form, expanded);
+                    }
+                }
                 List(list).expand_once(env)
             }
             _ => Ok(self.clone()),
@@ -89,28 +108,55 @@ impl Expr {
         }
     }
 
+    /// Calls a function value with already evaluated arguments.
+    pub(super) fn apply(&self, args: Vec<Expr>, env: &mut Env) -> Result<Expr, LispError> {
+        match self {
+            // Builtins evaluate their own arguments, so keep them from being evaluated twice.
+            Expr::Fn(func) => {
+                let quoted: Vec<Expr> = args
+                    .into_iter()
+                    .map(|arg| Expr::List(vec![Expr::Symbol("quote".to_string()), arg]))
+                    .collect();
+                func(&quoted, env)
+            }
+            Expr::Lambda(lambda) => {
+                let new_env = &mut create_scope(&lambda.bindings, &args, env)?;
+                lambda.body.eval(new_env)
+            }
+            not_a_fn => Err(LispError::TypeMismatch(Type::Fn, not_a_fn.clone())),
+        }
+    }
+
     pub fn eval(&self, env: &mut Env) -> Result<Self, LispError> {
         use Expr::*;
         use LispError::*;
 
+        env.record(|stats| stats.forms_evaluated += 1);
         match self {
             Float(n) => Ok(Float(*n)),
             Bool(n) => Ok(Bool(*n)),
             String(s) => Ok(String(s.to_string())),
+            Bytes(b) => Ok(Bytes(b.clone())),
+            Handle(h) => Ok(Handle(h.clone())),
             Symbol(s) => {
                 let data = env.get(s).ok_or_else(|| SymbolNotFound(s.to_string()))?;
                 Ok(data)
             }
             List(list) => match &list[..] {
-                [first, rest @ ..] => match first.eval(env)? {
-                    Fn(func) => func(rest, env),
-                    Lambda(lambda) => {
-                        let args = eval_forms(rest, env)?;
-                        let new_env = &mut create_scope(&lambda.bindings, &args, env)?;
-                        lambda.body.eval(new_env)
+                [first, rest @ ..] => {
+                    let result = match first.eval(env)? {
+                        Fn(func) => func(rest, env),
+                        lambda @ Lambda(_) => {
+                            let args = eval_forms(rest, env)?;
+                            lambda.apply(args, env)
+                        }
+                        not_a_fn => Err(TypeMismatch(Type::Fn, not_a_fn)),
+                    }?;
+                    if let List(_) = result {
+                        env.record(|stats| stats.lists_allocated += 1);
                     }
-                    not_a_fn => Err(TypeMismatch(Type::Fn, not_a_fn)),
-                },
+                    Ok(result)
+                }
                 _ => Err(MalformedList(list.clone())),
             },
             Fn(x) => Err(TypeMismatch(Type::List, Fn(*x))),
@@ -136,8 +182,15 @@ fn create_scope<'a>(
         data.insert(k, v.clone());
     }
 
+    let depth = outer_env.depth + 1;
+    outer_env.record_depth(depth);
     Ok(Env {
         data,
+        io: outer_env.io.clone(),
+        stats: outer_env.stats.clone(),
+        trace: outer_env.trace.clone(),
+        dbg_locations: outer_env.dbg_locations.clone(),
+        depth,
         outer: Some(outer_env),
     })
 }
@@ -169,6 +222,8 @@ impl fmt::Debug for Expr {
             Self::Lambda(arg0) => f.debug_tuple("Lambda").field(arg0).finish(),
             Self::Symbol(arg0) => f.debug_tuple("Symbol").field(arg0).finish(),
             Self::String(arg0) => f.debug_tuple("String").field(arg0).finish(),
+            Self::Bytes(arg0) => f.debug_tuple("Bytes").field(arg0).finish(),
+            Self::Handle(arg0) => f.debug_tuple("Handle").field(&arg0.name()).finish(),
             Self::Float(arg0) => f.debug_tuple("Float").field(arg0).finish(),
             Self::List(arg0) => f.debug_tuple("List").field(arg0).finish(),
             Self::Bool(arg0) => f.debug_tuple("Bool").field(arg0).finish(),
@@ -177,11 +232,22 @@ impl fmt::Debug for Expr {
     }
 }
 
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let str = match self {
             Self::Symbol(s) => s.clone(),
             Self::String(s) => format!(r#""{}""#, s),
+            Self::Bytes(b) => format!("#<bytes {}>", super::encoding::hex_encode(b)),
+            Self::Handle(h) => match h.name() {
+                Some(name) => format!("#<handle {}>", name),
+                None => "#<handle closed>".to_string(),
+            },
             Self::Bool(b) => b.to_string(),
             Self::Float(n) => n.to_string(),
             Self::Fn(_) => "#<builtin>".to_string(),
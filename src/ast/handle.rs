@@ -0,0 +1,160 @@
+//! Handles to host objects such as files, which are closed once the last
+//! reference to them is dropped or they're explicitly `close!`d.
+use super::LispError;
+use std::{
+    any::Any,
+    cell::RefCell,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    rc::Rc,
+};
+
+pub trait Resource: Any {
+    /// Name shown when the handle is printed.
+    fn name(&self) -> &'static str;
+
+    /// Finalizer, releases whatever the resource holds.
+    fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct Slot(Option<Box<dyn Resource>>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some(mut resource) = self.0.take() {
+            let _ = resource.close();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Handle(Rc<RefCell<Slot>>);
+
+impl Handle {
+    pub fn new(resource: impl Resource) -> Self {
+        Handle(Rc::new(RefCell::new(Slot(Some(Box::new(resource))))))
+    }
+
+    /// Runs the finalizer now, returning false if the handle was already closed.
+    pub fn close(&self) -> Result<bool, LispError> {
+        let resource = self.0.borrow_mut().0.take();
+        match resource {
+            Some(mut resource) => {
+                resource.close()?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.0.borrow().0.is_none()
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        self.0.borrow().0.as_ref().map(|resource| resource.name())
+    }
+
+    pub fn ptr_eq(&self, other: &Handle) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Borrows the resource as an `R`, failing if it's closed or a different kind of resource.
+    pub fn with<R: Resource, T>(
+        &self,
+        f: impl FnOnce(&mut R) -> Result<T, LispError>,
+    ) -> Result<T, LispError> {
+        let mut slot = self.0.borrow_mut();
+        let resource = slot
+            .0
+            .as_mut()
+            .ok_or_else(|| LispError::InvalidArgument("handle is closed".to_string()))?;
+        let name = resource.name();
+        match resource.as_any_mut().downcast_mut::<R>() {
+            Some(resource) => f(resource),
+            None => Err(LispError::InvalidArgument(format!(
+                "expected a different kind of handle, got {name}"
+            ))),
+        }
+    }
+}
+
+pub struct FileResource(File);
+
+impl FileResource {
+    /// Opens `path` with a mode of "r" (read), "w" (truncate and write) or "a" (append).
+    pub fn open(path: &str, mode: &str) -> Result<Self, LispError> {
+        let mut options = OpenOptions::new();
+        match mode {
+            "r" => options.read(true),
+            "w" => options.write(true).create(true).truncate(true),
+            "a" => options.append(true).create(true),
+            _ => {
+                return Err(LispError::InvalidArgument(format!(
+                    "unknown file mode {mode:?}, expected \"r\", \"w\" or \"a\""
+                )))
+            }
+        };
+        Ok(FileResource(options.open(path)?))
+    }
+
+    pub fn read_to_string(&mut self) -> Result<String, LispError> {
+        let mut buf = String::new();
+        self.0.read_to_string(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn write(&mut self, s: &str) -> Result<(), LispError> {
+        Ok(self.0.write_all(s.as_bytes())?)
+    }
+}
+
+impl Resource for FileResource {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn handles_are_finalized_on_close_and_drop() {
+    struct Flag(Rc<RefCell<u32>>);
+    impl Resource for Flag {
+        fn name(&self) -> &'static str {
+            "flag"
+        }
+        fn close(&mut self) -> io::Result<()> {
+            *self.0.borrow_mut() += 1;
+            Ok(())
+        }
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    let closed = Rc::new(RefCell::new(0));
+    let handle = Handle::new(Flag(closed.clone()));
+    let copy = handle.clone();
+    drop(handle);
+    assert_eq!(*closed.borrow(), 0);
+    drop(copy);
+    assert_eq!(*closed.borrow(), 1);
+
+    let handle = Handle::new(Flag(closed.clone()));
+    assert!(handle.close().unwrap());
+    assert!(!handle.close().unwrap());
+    assert!(handle.is_closed());
+    drop(handle);
+    assert_eq!(*closed.borrow(), 2);
+}
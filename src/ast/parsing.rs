@@ -1,8 +1,10 @@
-use crate::ast::Expr;
+use crate::ast::expr::{Expr, Location};
 use chumsky::prelude::*;
 use chumsky::Parser;
+use std::{cell::RefCell, ops::Range, rc::Rc};
 
-pub fn parse_expr() -> impl Parser<char, Expr, Error = Simple<char>> {
+/// Parses a single expression, noting in `source_map` where every `(dbg form)` was read from.
+pub fn parse_expr(source_map: Rc<SourceMap>) -> impl Parser<char, Expr, Error = Simple<char>> {
     let positive_num = text::int(10).from_str::<i64>().unwrapped();
     let negative_num = just('-').then(positive_num).map(|x| -x.1);
     let float = negative_num
@@ -44,8 +46,11 @@ pub fn parse_expr() -> impl Parser<char, Expr, Error = Simple<char>> {
         choice((
             expr.padded()
                 .repeated()
-                .map(Expr::List)
-                .delimited_by(just("("), just(")")),
+                .delimited_by(just("("), just(")"))
+                .map_with_span(move |list, span| {
+                    source_map.note_dbg(&list, span);
+                    Expr::List(list)
+                }),
             float.map(Expr::Float),
             bool,
             string,
@@ -56,12 +61,69 @@ pub fn parse_expr() -> impl Parser<char, Expr, Error = Simple<char>> {
 
     expr
 }
-pub fn parse_script() -> impl Parser<char, Vec<Expr>, Error = Simple<char>> {
-    let expr = parse_expr();
+
+pub fn parse_script(
+    source_map: Rc<SourceMap>,
+) -> impl Parser<char, Vec<Expr>, Error = Simple<char>> {
+    let expr = parse_expr(source_map);
 
     expr.padded().repeated()
 }
 
+/// Maps character offsets in reader macro expanded text back to the source it was expanded from.
+pub struct SourceMap {
+    source: String,
+    /// Byte offset into `source` for every char of the expanded text.
+    origins: Vec<usize>,
+    /// Printed form of each `(dbg form)` read so far, with where it was.
+    dbg_forms: RefCell<Vec<(String, Location)>>,
+}
+
+impl SourceMap {
+    /// Applies reader macros to `source`, returning the expanded text and its map.
+    pub fn expand(source: &str) -> (String, SourceMap) {
+        let (expanded, byte_origins) = reader_macros::apply_reader_macros_mapped(source);
+        let origins = expanded
+            .char_indices()
+            .map(|(i, _)| byte_origins[i])
+            .collect();
+        let source_map = SourceMap {
+            source: source.to_string(),
+            origins,
+            dbg_forms: RefCell::default(),
+        };
+        (expanded, source_map)
+    }
+
+    fn note_dbg(&self, list: &[Expr], span: Range<usize>) {
+        if let [Expr::Symbol(s), form] = list {
+            if s == "dbg" {
+                let at = self.locate(span.start);
+                self.dbg_forms.borrow_mut().push((form.to_string(), at));
+            }
+        }
+    }
+
+    /// Takes the `(dbg form)`s noted while parsing, as printed forms and where they were read.
+    pub fn take_dbg_forms(&self) -> Vec<(String, Location)> {
+        self.dbg_forms.take()
+    }
+
+    pub fn locate(&self, offset: usize) -> Location {
+        let byte = self
+            .origins
+            .get(offset)
+            .copied()
+            .unwrap_or(self.source.len());
+        let before = &self.source[..byte];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Location {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
 pub mod reader_macros {
     macro_rules! reader_macro {
     ($($k:expr => $v:expr),+ $(,)? ) => {{
@@ -71,8 +133,16 @@ pub mod reader_macros {
     }};
 }
 
+    #[cfg(test)]
     pub fn apply_reader_macros(input: &str) -> String {
+        apply_reader_macros_mapped(input).0
+    }
+
+    /// Applies reader macros, also returning the byte offset in `input`
+    /// that every byte of the result came from.
+    pub fn apply_reader_macros_mapped(input: &str) -> (String, Vec<usize>) {
         let mut result = input.to_string();
+        let mut origins: Vec<usize> = (0..input.len()).collect();
         let lut = reader_macro!(
             "'" => "quote",
             "`" => "quasiquote",
@@ -86,20 +156,30 @@ pub mod reader_macros {
 
                 result = result.replacen(k, "", 1);
                 result.insert_str(idx, v);
+                let origin = origins.remove(idx);
+                origins.splice(idx..idx, std::iter::repeat_n(origin, v.len()));
 
                 let offset = idx + v.len();
-                if let Some(idx) = &result[offset..].find(' ') && !starts_with_bracket {
-                        result.insert(offset + idx, ')');
+                let close_at =
+                    if let Some(idx) = &result[offset..].find(' ') && !starts_with_bracket {
+                        Some(offset + idx)
                     } else {
-                        match find_end_of_expr(&result[offset..]) {
-                            Some(idx) => result.insert(offset+idx, ')'),
-                            None => result.push(')'),
-                        };
+                        find_end_of_expr(&result[offset..]).map(|idx| offset + idx)
+                    };
+                match close_at {
+                    Some(idx) => {
+                        result.insert(idx, ')');
+                        origins.insert(idx, origins[idx - 1]);
                     }
+                    None => {
+                        result.push(')');
+                        origins.push(origins.last().copied().unwrap_or(0));
+                    }
+                }
             }
         }
 
-        result
+        (result, origins)
     }
 
     fn find_end_of_expr(input: &str) -> Option<usize> {
@@ -125,6 +205,18 @@ pub mod reader_macros {
         None
     }
 
+    #[test]
+    fn reader_macros_map_back_to_source() {
+        use super::SourceMap;
+
+        let (expanded, source_map) = SourceMap::expand("(def x '(a))\n  (dbg ,y )");
+        assert_eq!(expanded, "(def x (quote (a)))\n  (dbg (unquote y) )");
+        let dbg = expanded.find("(dbg").unwrap();
+        assert_eq!(source_map.locate(dbg).to_string(), "2:3");
+        let y = expanded.find('y').unwrap();
+        assert_eq!(source_map.locate(y).to_string(), "2:9");
+    }
+
     #[test]
     fn quasiquote_reader_macro() {
         let input = r#"`(def ,name (fn ,args ^body))"#;
@@ -0,0 +1,213 @@
+//! A tiny persistent key-value store for `store-open` and friends.
+//!
+//! The file is an append-only log with one `(key value)` form per line,
+//! later entries overriding earlier ones when the store is opened.
+use super::{expr::Expr, handle::Resource, LispError};
+use rustc_hash::FxHashMap as HashMap;
+use std::{
+    any::Any,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    iter::Peekable,
+    str::Chars,
+};
+
+pub struct Store {
+    file: File,
+    /// Keyed by the printed form of the key, since `Expr` isn't hashable.
+    entries: HashMap<String, Expr>,
+}
+
+impl Store {
+    pub fn open(path: &str) -> Result<Self, LispError> {
+        let log = match fs::read_to_string(path) {
+            Ok(log) => log,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        // Every entry ends in a newline, so anything after the last one is a torn write.
+        let complete = log.rfind('\n').map_or(0, |i| i + 1);
+        let entries = read_entries(&log[..complete])
+            .ok_or_else(|| LispError::InvalidArgument(format!("store {path:?} is corrupt")))?;
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        if complete < log.len() {
+            file.set_len(complete as u64)?;
+        }
+        Ok(Store { file, entries })
+    }
+
+    pub fn get(&self, key: &Expr) -> Option<Expr> {
+        self.entries.get(&key.to_string()).cloned()
+    }
+
+    pub fn put(&mut self, key: Expr, value: Expr) -> Result<(), LispError> {
+        check_persistable(&key)?;
+        check_persistable(&value)?;
+        writeln!(self.file, "({} {})", key, value)?;
+        self.entries.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+/// Reads the whole log back, failing on anything that isn't a `(key value)` entry.
+/// This doesn't use the script parser, since floats need to read back exactly as `Display` wrote them.
+fn read_entries(log: &str) -> Option<Vec<(Expr, Expr)>> {
+    let mut chars = log.chars().peekable();
+    let mut entries = vec![];
+    loop {
+        skip_whitespace(&mut chars);
+        if chars.peek().is_none() {
+            return Some(entries);
+        }
+        match read_form(&mut chars)? {
+            Expr::List(mut entry) if entry.len() == 2 => {
+                let value = entry.pop()?;
+                entries.push((entry.pop()?, value));
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn is_atom_char(c: &char) -> bool {
+    !c.is_whitespace() && !matches!(c, '(' | ')' | '"')
+}
+
+fn read_form(chars: &mut Peekable<Chars>) -> Option<Expr> {
+    skip_whitespace(chars);
+    match chars.next()? {
+        '(' => {
+            let mut list = vec![];
+            loop {
+                skip_whitespace(chars);
+                if chars.next_if_eq(&')').is_some() {
+                    return Some(Expr::List(list));
+                }
+                list.push(read_form(chars)?);
+            }
+        }
+        '"' => {
+            let mut string = String::new();
+            loop {
+                match chars.next()? {
+                    '"' => return Some(Expr::String(string)),
+                    c => string.push(c),
+                }
+            }
+        }
+        ')' => None,
+        c => {
+            let mut atom = String::from(c);
+            while let Some(c) = chars.next_if(is_atom_char) {
+                atom.push(c);
+            }
+            Some(read_atom(atom))
+        }
+    }
+}
+
+fn read_atom(atom: String) -> Expr {
+    match atom.as_str() {
+        "true" => Expr::Bool(true),
+        "false" => Expr::Bool(false),
+        _ => match atom.parse() {
+            Ok(n) => Expr::Float(n),
+            Err(_) => Expr::Symbol(atom),
+        },
+    }
+}
+
+/// Only values which read back as themselves, and fit on the one line
+/// torn write recovery expects of an entry, can be stored.
+fn check_persistable(expr: &Expr) -> Result<(), LispError> {
+    match expr {
+        Expr::Float(_) | Expr::Bool(_) => Ok(()),
+        Expr::Symbol(s)
+            if s.chars().all(|c| is_atom_char(&c))
+                && matches!(read_atom(s.clone()), Expr::Symbol(_)) =>
+        {
+            Ok(())
+        }
+        Expr::String(s) if !s.contains(['"', '\n']) => Ok(()),
+        Expr::List(list) => list.iter().try_for_each(check_persistable),
+        not_persistable => Err(LispError::InvalidArgument(format!(
+            "{not_persistable} can't be written to a store"
+        ))),
+    }
+}
+
+impl Resource for Store {
+    fn name(&self) -> &'static str {
+        "store"
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn store_persists_between_opens() {
+    let path = std::env::temp_dir().join(format!("wilf-store-{}.db", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = fs::remove_file(path);
+    let key = Expr::String("count".to_string());
+
+    let mut store = Store::open(path).unwrap();
+    assert!(store.get(&key).is_none());
+    store.put(key.clone(), Expr::Float(1.0)).unwrap();
+    store
+        .put(
+            key.clone(),
+            Expr::List(vec![Expr::Float(2.0), Expr::Bool(true)]),
+        )
+        .unwrap();
+    assert!(store.put(key.clone(), Expr::Bytes(vec![])).is_err());
+    drop(store);
+
+    let store = Store::open(path).unwrap();
+    assert_eq!(store.get(&key).unwrap().to_string(), "(2 true)");
+    drop(store);
+
+    let mut store = Store::open(path).unwrap();
+    let floats = [0.001, -0.5, 1e20, f64::INFINITY];
+    let floats = Expr::List(floats.into_iter().map(Expr::Float).collect());
+    store.put(key.clone(), floats.clone()).unwrap();
+    assert!(store
+        .put(key.clone(), Expr::Symbol("inf".to_string()))
+        .is_err());
+    assert!(store
+        .put(key.clone(), Expr::String("line1\nline2".to_string()))
+        .is_err());
+    drop(store);
+    let store = Store::open(path).unwrap();
+    assert!(super::diff::equal(&store.get(&key).unwrap(), &floats));
+    drop(store);
+
+    // A torn last write is dropped, but garbage before it isn't.
+    fs::write(path, "(\"a\" 1)\n(\"b\" 2").unwrap();
+    let mut store = Store::open(path).unwrap();
+    assert!(store.get(&Expr::String("b".to_string())).is_none());
+    store.put(key.clone(), Expr::Float(3.0)).unwrap();
+    drop(store);
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        "(\"a\" 1)\n(\"count\" 3)\n"
+    );
+    fs::write(path, "(\"a\" 1)\n(\"b\" 2\n(\"c\" 3)\n").unwrap();
+    assert!(Store::open(path).is_err());
+    fs::remove_file(path).unwrap();
+}
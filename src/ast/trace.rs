@@ -0,0 +1,157 @@
+//! Record and replay of nondeterministic builtins.
+//!
+//! When recording, every result of a builtin like `readline` or `rand` is
+//! appended to a trace file, one `name kind value` line each. Replaying hands
+//! those results back in the same order instead of asking the outside world,
+//! so a run can be reproduced exactly.
+use super::{encoding, expr::Expr, LispError};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug)]
+enum Mode {
+    Live,
+    Record(File),
+    Replay(VecDeque<(String, Expr)>),
+}
+
+#[derive(Debug)]
+pub struct Trace {
+    mode: Mode,
+    /// xorshift state for `rand`.
+    rng: u64,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Trace {
+            mode: Mode::Live,
+            rng: seed | 1,
+        }
+    }
+}
+
+impl Trace {
+    pub fn record(path: &Path) -> Result<Self, LispError> {
+        Ok(Trace {
+            mode: Mode::Record(File::create(path)?),
+            ..Trace::default()
+        })
+    }
+
+    pub fn replay(path: &Path) -> Result<Self, LispError> {
+        let corrupt = |line: &str| LispError::InvalidArgument(format!("bad trace entry {line:?}"));
+        let entries = fs::read_to_string(path)?
+            .lines()
+            .map(|line| {
+                let mut parts = line.splitn(3, ' ');
+                let (Some(name), Some(kind), Some(value)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(corrupt(line));
+                };
+                let value = match kind {
+                    "string" => Expr::String(encoding::url_decode(value)?),
+                    "float" => Expr::Float(value.parse().map_err(|_| corrupt(line))?),
+                    _ => return Err(corrupt(line)),
+                };
+                Ok((name.to_string(), value))
+            })
+            .collect::<Result<_, LispError>>()?;
+        Ok(Trace {
+            mode: Mode::Replay(entries),
+            ..Trace::default()
+        })
+    }
+
+    /// Runs `live` for the result of the builtin `name`, unless replaying,
+    /// in which case the next recorded result is used instead.
+    pub fn nondeterministic(
+        &mut self,
+        name: &str,
+        live: impl FnOnce(&mut Self) -> Result<Expr, LispError>,
+    ) -> Result<Expr, LispError> {
+        if let Mode::Replay(entries) = &mut self.mode {
+            return match entries.pop_front() {
+                Some((recorded, value)) if recorded == name => Ok(value),
+                Some((recorded, _)) => Err(LispError::InvalidArgument(format!(
+                    "replay diverged, expected {recorded} but got {name}"
+                ))),
+                None => Err(LispError::InvalidArgument(format!(
+                    "replay trace ran out before {name}"
+                ))),
+            };
+        }
+
+        let value = live(self)?;
+        if let Mode::Record(file) = &mut self.mode {
+            let (kind, encoded) = match &value {
+                Expr::String(s) => ("string", encoding::url_encode(s.as_bytes())),
+                Expr::Float(n) => ("float", n.to_string()),
+                not_traceable => {
+                    return Err(LispError::InvalidArgument(format!(
+                        "{not_traceable} can't be recorded in a trace"
+                    )))
+                }
+            };
+            writeln!(file, "{name} {kind} {encoded}")?;
+        }
+        Ok(value)
+    }
+
+    /// Checks a replay used up the whole trace, as a run asking for fewer values has diverged too.
+    pub fn finish(&self) -> Result<(), LispError> {
+        match &self.mode {
+            Mode::Replay(entries) if !entries.is_empty() => {
+                Err(LispError::InvalidArgument(format!(
+                    "replay finished with {} unused trace entries, starting with {}",
+                    entries.len(),
+                    entries[0].0
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// A random number in `[0, 1)`.
+    pub fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[test]
+fn replay_returns_recorded_results() {
+    let path = std::env::temp_dir().join(format!("wilf-trace-{}.txt", std::process::id()));
+    let line = Expr::String("two words & \"quotes\"".to_string());
+
+    let mut trace = Trace::record(&path).unwrap();
+    let random = trace
+        .nondeterministic("rand", |trace| Ok(Expr::Float(trace.random())))
+        .unwrap();
+    trace
+        .nondeterministic("readline", |_| Ok(line.clone()))
+        .unwrap();
+    drop(trace);
+
+    let mut trace = Trace::replay(&path).unwrap();
+    let unreachable = |_: &mut Trace| -> Result<Expr, LispError> { panic!("replay went live") };
+    let replayed = trace.nondeterministic("rand", unreachable).unwrap();
+    assert_eq!(replayed.to_string(), random.to_string());
+    assert!(trace.finish().is_err());
+    let replayed = trace.nondeterministic("readline", unreachable).unwrap();
+    assert_eq!(replayed.to_string(), line.to_string());
+    assert!(trace.finish().is_ok());
+    assert!(trace.nondeterministic("readline", unreachable).is_err());
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,94 @@
+//! Filesystem watching for `watch-path`, backed by `notify`.
+use super::{expr::Expr, handle::Resource, LispError};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use std::{
+    any::Any,
+    collections::VecDeque,
+    io,
+    path::Path,
+    sync::mpsc::{channel, Receiver},
+};
+
+pub struct Watcher {
+    /// Kept around since dropping it stops the watch.
+    watcher: Option<RecommendedWatcher>,
+    events: Receiver<notify::Result<Event>>,
+    /// A single notify event can cover several paths, which are handed out one at a time.
+    pending: VecDeque<Expr>,
+}
+
+fn to_lisp_error(err: notify::Error) -> LispError {
+    LispError::Io(io::Error::other(err))
+}
+
+impl Watcher {
+    pub fn new(path: &str) -> Result<Self, LispError> {
+        let (tx, events) = channel();
+        let mut watcher = notify::recommended_watcher(tx).map_err(to_lisp_error)?;
+        watcher
+            .watch(Path::new(path), RecursiveMode::Recursive)
+            .map_err(to_lisp_error)?;
+        Ok(Watcher {
+            watcher: Some(watcher),
+            events,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Blocks until the next change, returned as `(kind path)`
+    /// where kind is one of `create`, `modify`, `remove`, `access` or `other`.
+    pub fn next(&mut self) -> Result<Expr, LispError> {
+        while self.pending.is_empty() {
+            let event = self
+                .events
+                .recv()
+                .map_err(|_| LispError::InvalidArgument("watcher has stopped".to_string()))?
+                .map_err(to_lisp_error)?;
+            let kind = match event.kind {
+                EventKind::Create(_) => "create",
+                EventKind::Modify(_) => "modify",
+                EventKind::Remove(_) => "remove",
+                EventKind::Access(_) => "access",
+                EventKind::Any | EventKind::Other => "other",
+            };
+            self.pending.extend(event.paths.iter().map(|path| {
+                Expr::List(vec![
+                    Expr::Symbol(kind.to_string()),
+                    Expr::String(path.to_string_lossy().into_owned()),
+                ])
+            }));
+        }
+        Ok(self.pending.pop_front().expect("pending is not empty"))
+    }
+}
+
+impl Resource for Watcher {
+    fn name(&self) -> &'static str {
+        "watcher"
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.watcher = None;
+        Ok(())
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn watcher_reports_created_files() {
+    let dir = std::env::temp_dir().join(format!("wilf-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut watcher = Watcher::new(dir.to_str().unwrap()).unwrap();
+
+    let file = dir.join("new.txt");
+    std::fs::write(&file, "hello").unwrap();
+    let event = watcher.next().unwrap();
+    assert_eq!(
+        event.to_string(),
+        format!(r#"(create "{}")"#, file.to_string_lossy())
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
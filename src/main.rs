@@ -2,14 +2,13 @@
 #![feature(let_chains)]
 use ::rustyline::error::ReadlineError;
 use ast::env::{self, Env};
-use ast::parsing::reader_macros::apply_reader_macros;
 pub use chumsky::{prelude::*, Parser};
 use clap::Parser as ArgParser;
 pub use std::{
     error::Error,
     io::{self, stdout, Write},
 };
-use std::{fs, path::PathBuf};
+use std::{fs, path::PathBuf, process};
 
 mod ast;
 mod rustyline;
@@ -18,22 +17,71 @@ mod rustyline;
 struct Args {
     /// File path to evaluate like a script.
     /// If ommitted, starts a repl.
+    /// Everything after SCRIPT is passed to it as `*args*`, rather than parsed by wilf.
     #[arg(short, long, value_name = "SCRIPT")]
     script: Option<PathBuf>,
+
+    /// Record the results of nondeterministic builtins, like readline and rand, to TRACE.
+    #[arg(long, value_name = "TRACE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replay the results of nondeterministic builtins from a recorded TRACE.
+    #[arg(long, value_name = "TRACE")]
+    replay: Option<PathBuf>,
+
+    /// Print interpreter statistics to stderr on exit.
+    #[arg(long)]
+    stats: bool,
+}
+
+/// Splits the command line just after the script path,
+/// so the script's own options like `--help` reach it untouched.
+fn split_script_args(mut argv: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let mut end = argv.len();
+    for (i, arg) in argv.iter().enumerate() {
+        if arg == "--" {
+            break;
+        } else if arg == "-s" || arg == "--script" {
+            end = (i + 2).min(argv.len());
+            break;
+        } else if arg.starts_with("--script=") || arg.starts_with("-s") {
+            end = i + 1;
+            break;
+        }
+    }
+    let script_args = argv.split_off(end);
+    (argv, script_args)
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+    let (argv, script_args) = split_script_args(std::env::args().collect());
+    let args = Args::parse_from(argv);
     let mut env = env::Env::default();
-    match args.script {
+    env.set_args(script_args);
+    if let Some(trace) = &args.record {
+        env.record_trace(trace)?;
+    }
+    if let Some(trace) = &args.replay {
+        env.replay_trace(trace)?;
+    }
+    let result = match args.script {
         Some(script) => eval_script(script, &mut env),
         None => repl(&mut env),
     }
+    .and_then(|()| Ok(env.finish_trace()?));
+    if args.stats {
+        eprintln!("{}", env.stats());
+    }
+    // Printed with Display rather than main's Debug, so e.g. assert= diffs stay readable.
+    if let Err(err) = result {
+        eprintln!("Error - {err}");
+        process::exit(1);
+    }
+    Ok(())
 }
 
 fn eval_script(script: PathBuf, env: &mut Env) -> Result<(), Box<dyn Error>> {
     let input = fs::read_to_string(script)?;
-    let input = apply_reader_macros(&input);
     ast::eval_script(&input, env)?;
     Ok(())
 }
@@ -54,7 +102,7 @@ fn repl(env: &mut Env) -> Result<(), Box<dyn Error>> {
                 rl.add_history_entry(line.as_str())?;
                 rl.save_history("wilf.history")?;
 
-                let result = match ast::eval_expr(&apply_reader_macros(&input?), env) {
+                let result = match ast::eval_expr(&input?, env) {
                     Ok(result) => result.to_string(),
                     Err(err) => format!("Error - {err}"),
                 };